@@ -1,31 +1,110 @@
 use std::collections::HashMap;
 
 use cranelift::codegen::entity::EntityRef;
+use cranelift::codegen::ir::condcodes::{FloatCC, IntCC};
 use cranelift::codegen::ir::types::*;
-use cranelift::codegen::ir::{AbiParam, Block, Function, InstBuilder, UserFuncName};
+use cranelift::codegen::ir::{AbiParam, Block, Function, InstBuilder, MemFlags, UserFuncName};
 use cranelift::codegen::settings;
 use cranelift::codegen::verifier::verify_function;
 use cranelift::frontend::{FunctionBuilder, FunctionBuilderContext, Variable};
 use cranelift::prelude::{Imm64, Value};
-use cranelift_module::{default_libcall_names, Linkage, Module};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module};
 use cranelift_native::builder;
 use cranelift_object::{ObjectBuilder, ObjectModule};
 
 use crate::parse::{
-    Block as BlockExpr, Bop, BuiltinType, Expression, Function as Func, Literal, Type as _, T,
+    Block as BlockExpr, Bop, BuiltinType, Expression, Function as Func, Literal, Span, Type as _, T,
 };
 use anyhow::Result;
 
+/// Errors produced while lowering the AST to Cranelift IR. Each variant
+/// carries the source span of the offending node so callers can render a
+/// proper diagnostic instead of panicking.
+#[derive(Debug, Clone)]
+pub enum CodegenError {
+    UndefinedIdentifier { name: String, span: Span },
+    UndefinedFunction { name: String, span: Span },
+    UnknownField { name: String, span: Span },
+    UnresolvedType { span: Span },
+    UnexpectedTopLevel { span: Span },
+}
+
+impl CodegenError {
+    fn span(&self) -> Span {
+        match self {
+            CodegenError::UndefinedIdentifier { span, .. } => *span,
+            CodegenError::UndefinedFunction { span, .. } => *span,
+            CodegenError::UnknownField { span, .. } => *span,
+            CodegenError::UnresolvedType { span } => *span,
+            CodegenError::UnexpectedTopLevel { span } => *span,
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            CodegenError::UndefinedIdentifier { name, .. } => {
+                format!("undefined identifier `{name}`")
+            }
+            CodegenError::UndefinedFunction { name, .. } => format!("undefined function `{name}`"),
+            CodegenError::UnknownField { name, .. } => format!("unknown field `{name}`"),
+            CodegenError::UnresolvedType { .. } => {
+                "hit the bottom type while generating code".to_string()
+            }
+            CodegenError::UnexpectedTopLevel { .. } => {
+                "top-level items must be functions".to_string()
+            }
+        }
+    }
+}
+
+impl std::fmt::Display for CodegenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.message())
+    }
+}
+
+impl std::error::Error for CodegenError {}
+
+/// Renders a [`CodegenError`] as a labeled snippet pointing at the offending
+/// span in `source`, the same way a compiler frontend would report it.
+pub fn render_codegen_error(file_name: &str, source: &str, err: &CodegenError) -> Result<()> {
+    use codespan_reporting::diagnostic::{Diagnostic, Label};
+    use codespan_reporting::files::SimpleFile;
+    use codespan_reporting::term::{
+        self,
+        termcolor::{ColorChoice, StandardStream},
+    };
+
+    let file = SimpleFile::new(file_name, source);
+    let span = err.span();
+    let diagnostic = Diagnostic::error()
+        .with_message(err.message())
+        .with_labels(vec![Label::primary((), span.start..span.end)]);
+
+    let writer = StandardStream::stderr(ColorChoice::Auto);
+    let config = term::Config::default();
+    term::emit(&mut writer.lock(), &config, &file, &diagnostic)?;
+
+    Ok(())
+}
+
 pub struct Ctx {
     variables: HashMap<String, Variable>,
     variable_counter: usize,
+    funcs: HashMap<String, FuncId>,
+    layouts: HashMap<String, RecordLayout>,
+    malloc: Option<FuncId>,
 }
 
 impl Ctx {
-    fn new() -> Self {
+    fn new(funcs: HashMap<String, FuncId>) -> Self {
         Self {
             variables: HashMap::new(),
             variable_counter: 0,
+            funcs,
+            layouts: HashMap::new(),
+            malloc: None,
         }
     }
 
@@ -45,15 +124,37 @@ impl Ctx {
     fn get_variable(&self, name: &str) -> Option<Variable> {
         self.variables.get(name).cloned()
     }
+
+    fn get_func(&self, name: &str) -> Option<FuncId> {
+        self.funcs.get(name).cloned()
+    }
+
+    fn record_layout(
+        &mut self,
+        name: &str,
+        fields: &[(String, T)],
+        span: Span,
+    ) -> Result<&RecordLayout, CodegenError> {
+        if !self.layouts.contains_key(name) {
+            let layout = compute_record_layout(fields, span)?;
+            self.layouts.insert(name.to_string(), layout);
+        }
+
+        Ok(self.layouts.get(name).expect("layout was just inserted"))
+    }
+
+    fn malloc_id<M: Module>(&mut self, module: &mut M) -> FuncId {
+        *self.malloc.get_or_insert_with(|| declare_malloc(module))
+    }
 }
 
 // converts a T to a cranelift Type
 // option represents the unit type
-fn type_to_cranelift(ty: &T) -> Option<Type> {
+fn type_to_cranelift(ty: &T, span: Span) -> Result<Option<Type>, CodegenError> {
     use T::*;
 
-    match ty {
-        Hole => panic!("Hit bottom type when translating to IR"),
+    let ty = match ty {
+        Hole => return Err(CodegenError::UnresolvedType { span }),
         Unit => None,
         BuiltIn(b) => match b {
             BuiltinType::Int => Some(I32),
@@ -61,118 +162,489 @@ fn type_to_cranelift(ty: &T) -> Option<Type> {
             BuiltinType::String | BuiltinType::Array => Some(I64), // ptr
             BuiltinType::Char | BuiltinType::Bool => Some(I8),
         },
-        Record(_)
+        Record(_, _)
         | Function {
             param_tys: _,
             return_ty: _,
         } => Some(I64), // ptr
+    };
+
+    Ok(ty)
+}
+
+// byte offset and cranelift type of each field, plus the total (8-byte
+// aligned) allocation size; computed once per record name and cached on
+// `Ctx` since every construction/access of that record needs it
+struct RecordLayout {
+    fields: HashMap<String, (i32, Type)>,
+    size: i64,
+}
+
+fn align_up(offset: u32, align: u32) -> u32 {
+    (offset + align - 1) / align * align
+}
+
+fn compute_record_layout(fields: &[(String, T)], span: Span) -> Result<RecordLayout, CodegenError> {
+    let mut offset: u32 = 0;
+    let mut layout = HashMap::new();
+
+    for (name, ty) in fields {
+        let field_ty = type_to_cranelift(ty, span)?.ok_or(CodegenError::UnresolvedType { span })?;
+        let size = field_ty.bytes();
+        offset = align_up(offset, size);
+        layout.insert(name.clone(), (offset as i32, field_ty));
+        offset += size;
     }
+
+    // natural alignment of the whole record is the alignment of its widest
+    // field, which is at most 8 (F64/pointer) for every type we emit
+    let size = align_up(offset, 8);
+
+    Ok(RecordLayout {
+        fields: layout,
+        size: size as i64,
+    })
 }
 
 fn translate_literal(literal: &Literal, builder: &mut FunctionBuilder<'_>) -> Value {
     match literal {
         Literal::Bool(b) => builder.ins().iconst(I8, *b as i64),
         Literal::Int(i) => builder.ins().iconst(I32, *i as i64),
+        Literal::Float(f) => builder.ins().f64const(*f),
     }
 }
 
-fn translate_assignment(
+fn translate_assignment<M: Module>(
     ident: &str,
     binding: &Expression,
     ty: T,
+    span: Span,
     builder: &mut FunctionBuilder<'_>,
+    module: &mut M,
     ctx: &mut Ctx,
-) {
-    let val = translate_expression(binding, builder, ctx);
-    let var = ctx.declare_variable(ident, builder, type_to_cranelift(&ty).unwrap());
+) -> Result<(), CodegenError> {
+    let val = translate_expression(binding, builder, module, ctx)?;
+    let cranelift_ty =
+        type_to_cranelift(&ty, span)?.ok_or(CodegenError::UnresolvedType { span })?;
+    let var = ctx.declare_variable(ident, builder, cranelift_ty);
     builder.def_var(var, val);
+    Ok(())
 }
 
-fn translate_infix(
+fn translate_infix<M: Module>(
     operation: &Bop,
     lhs: &Expression,
     rhs: &Expression,
+    operand_ty: &T,
     builder: &mut FunctionBuilder<'_>,
+    module: &mut M,
     ctx: &mut Ctx,
-) -> Value {
-    let left_val = translate_expression(lhs, builder, ctx);
-    let right_val = translate_expression(rhs, builder, ctx);
+) -> Result<Value, CodegenError> {
+    // `&&`/`||` must not evaluate the rhs unless the lhs leaves it live, so
+    // they get their own block-based lowering instead of eager operands
+    if matches!(operation, Bop::And | Bop::Or) {
+        return translate_short_circuit(operation, lhs, rhs, builder, module, ctx);
+    }
 
-    match operation {
+    let left_val = translate_expression(lhs, builder, module, ctx)?;
+    let right_val = translate_expression(rhs, builder, module, ctx)?;
+    let is_float = matches!(operand_ty, T::BuiltIn(BuiltinType::Float));
+
+    let result = match operation {
+        Bop::Plus if is_float => builder.ins().fadd(left_val, right_val),
         Bop::Plus => builder.ins().iadd(left_val, right_val),
+        Bop::Min if is_float => builder.ins().fsub(left_val, right_val),
         Bop::Min => builder.ins().isub(left_val, right_val),
+        Bop::Mul if is_float => builder.ins().fmul(left_val, right_val),
         Bop::Mul => builder.ins().imul(left_val, right_val),
+        Bop::Div if is_float => builder.ins().fdiv(left_val, right_val),
         Bop::Div => builder.ins().sdiv(left_val, right_val),
-        _ => unimplemented!(),
-    }
+        Bop::Eq if is_float => builder.ins().fcmp(FloatCC::Equal, left_val, right_val),
+        Bop::Eq => builder.ins().icmp(IntCC::Equal, left_val, right_val),
+        Bop::Neq if is_float => builder.ins().fcmp(FloatCC::NotEqual, left_val, right_val),
+        Bop::Neq => builder.ins().icmp(IntCC::NotEqual, left_val, right_val),
+        Bop::Lt if is_float => builder.ins().fcmp(FloatCC::LessThan, left_val, right_val),
+        Bop::Lt => builder
+            .ins()
+            .icmp(IntCC::SignedLessThan, left_val, right_val),
+        Bop::Lte if is_float => builder
+            .ins()
+            .fcmp(FloatCC::LessThanOrEqual, left_val, right_val),
+        Bop::Lte => builder
+            .ins()
+            .icmp(IntCC::SignedLessThanOrEqual, left_val, right_val),
+        Bop::Gt if is_float => builder
+            .ins()
+            .fcmp(FloatCC::GreaterThan, left_val, right_val),
+        Bop::Gt => builder
+            .ins()
+            .icmp(IntCC::SignedGreaterThan, left_val, right_val),
+        Bop::Gte if is_float => {
+            builder
+                .ins()
+                .fcmp(FloatCC::GreaterThanOrEqual, left_val, right_val)
+        }
+        Bop::Gte => builder
+            .ins()
+            .icmp(IntCC::SignedGreaterThanOrEqual, left_val, right_val),
+        Bop::And | Bop::Or => unreachable!("handled above via translate_short_circuit"),
+    };
+
+    Ok(result)
 }
 
-fn translate_block(b: &BlockExpr, builder: &mut FunctionBuilder<'_>, ctx: &mut Ctx) -> Block {
+// lowers `&&`/`||` to the same create-block/brif/merge-block shape as
+// `if`/`else`, so the rhs is only evaluated when its value can change the
+// result
+fn translate_short_circuit<M: Module>(
+    operation: &Bop,
+    lhs: &Expression,
+    rhs: &Expression,
+    builder: &mut FunctionBuilder<'_>,
+    module: &mut M,
+    ctx: &mut Ctx,
+) -> Result<Value, CodegenError> {
+    let left_val = translate_expression(lhs, builder, module, ctx)?;
+
+    let rhs_block = builder.create_block();
+    let merge_block = builder.create_block();
+    builder.append_block_param(merge_block, I8);
+
+    match operation {
+        // false && rhs -> false without evaluating rhs
+        Bop::And => builder
+            .ins()
+            .brif(left_val, rhs_block, &[], merge_block, &[left_val]),
+        // true || rhs -> true without evaluating rhs
+        Bop::Or => builder
+            .ins()
+            .brif(left_val, merge_block, &[left_val], rhs_block, &[]),
+        _ => unreachable!("only And/Or reach translate_short_circuit"),
+    };
+
+    builder.switch_to_block(rhs_block);
+    builder.seal_block(rhs_block); // single predecessor: the lhs block
+    let right_val = translate_expression(rhs, builder, module, ctx)?;
+    builder.ins().jump(merge_block, &[right_val]);
+
+    builder.switch_to_block(merge_block);
+    builder.seal_block(merge_block);
+
+    Ok(builder.block_params(merge_block)[0])
+}
+
+fn translate_block<M: Module>(
+    b: &BlockExpr,
+    builder: &mut FunctionBuilder<'_>,
+    module: &mut M,
+    ctx: &mut Ctx,
+) -> Result<Block, CodegenError> {
     let object_block = builder.create_block();
     for inst in &b.instructions {
-        let _ = translate_expression(inst, builder, ctx);
+        let _ = translate_expression(inst, builder, module, ctx)?;
+    }
+
+    Ok(object_block)
+}
+
+// true when the expression always transfers control away (e.g. via a
+// `return`), so the block it terminates must not also fall through into a
+// merge block.
+fn expr_diverges(expr: &Expression) -> bool {
+    match expr {
+        Expression::Return(_) => true,
+        Expression::Block(b) => b.instructions.last().is_some_and(expr_diverges),
+        // an if/else only diverges if neither arm can fall through
+        Expression::If { then, else_, .. } => expr_diverges(then) && expr_diverges(else_),
+        _ => false,
+    }
+}
+
+fn translate_if<M: Module>(
+    cond: &Expression,
+    then: &Expression,
+    else_: &Expression,
+    result_ty: Option<Type>,
+    builder: &mut FunctionBuilder<'_>,
+    module: &mut M,
+    ctx: &mut Ctx,
+) -> Result<Value, CodegenError> {
+    let cond_val = translate_expression(cond, builder, module, ctx)?;
+
+    let then_block = builder.create_block();
+    let else_block = builder.create_block();
+
+    let then_diverges = expr_diverges(then);
+    let else_diverges = expr_diverges(else_);
+
+    // if both arms diverge there is nothing to merge into, so don't even
+    // create the block
+    let merge_block = if then_diverges && else_diverges {
+        None
+    } else {
+        let block = builder.create_block();
+        if let Some(ty) = result_ty {
+            builder.append_block_param(block, ty);
+        }
+        Some(block)
+    };
+
+    builder
+        .ins()
+        .brif(cond_val, then_block, &[], else_block, &[]);
+
+    builder.switch_to_block(then_block);
+    builder.seal_block(then_block); // single predecessor: the cond block
+    let then_val = translate_expression(then, builder, module, ctx)?;
+    if !then_diverges {
+        let merge_block = merge_block.expect("a non-diverging arm implies a merge block");
+        match result_ty {
+            Some(_) => builder.ins().jump(merge_block, &[then_val]),
+            None => builder.ins().jump(merge_block, &[]),
+        };
     }
 
-    object_block
+    builder.switch_to_block(else_block);
+    builder.seal_block(else_block); // single predecessor: the cond block
+    let else_val = translate_expression(else_, builder, module, ctx)?;
+    if !else_diverges {
+        let merge_block = merge_block.expect("a non-diverging arm implies a merge block");
+        match result_ty {
+            Some(_) => builder.ins().jump(merge_block, &[else_val]),
+            None => builder.ins().jump(merge_block, &[]),
+        };
+    }
+
+    let result = match merge_block {
+        Some(block) => {
+            builder.switch_to_block(block);
+            builder.seal_block(block);
+            match result_ty {
+                Some(_) => builder.block_params(block)[0],
+                None => builder.ins().iconst(I64, 0), // placeholder nullptr
+            }
+        }
+        // both arms diverged, so this value can never actually be observed
+        None => else_val,
+    };
+
+    Ok(result)
+}
+
+fn translate_while<M: Module>(
+    cond: &Expression,
+    body: &Expression,
+    builder: &mut FunctionBuilder<'_>,
+    module: &mut M,
+    ctx: &mut Ctx,
+) -> Result<Value, CodegenError> {
+    let header_block = builder.create_block();
+    let body_block = builder.create_block();
+    let exit_block = builder.create_block();
+
+    builder.ins().jump(header_block, &[]);
+
+    // header_block has two predecessors (the jump above and the back edge
+    // emitted below), so it can't be sealed until both are known
+    builder.switch_to_block(header_block);
+    let cond_val = translate_expression(cond, builder, module, ctx)?;
+    builder
+        .ins()
+        .brif(cond_val, body_block, &[], exit_block, &[]);
+
+    builder.switch_to_block(body_block);
+    builder.seal_block(body_block); // single predecessor: the header block
+    let body_diverges = expr_diverges(body);
+    let _ = translate_expression(body, builder, module, ctx)?;
+    if !body_diverges {
+        builder.ins().jump(header_block, &[]);
+    }
+
+    builder.seal_block(header_block);
+
+    builder.switch_to_block(exit_block);
+    builder.seal_block(exit_block);
+
+    Ok(builder.ins().iconst(I64, 0)) // placeholder nullptr, loops yield unit
+}
+
+// declares the libc `malloc` with a C signature so record literals can
+// allocate their backing storage on the heap
+fn declare_malloc<M: Module>(module: &mut M) -> FuncId {
+    let mut sig = module.make_signature();
+    sig.call_conv = module.target_config().default_call_conv;
+    sig.params.push(AbiParam::new(I64));
+    sig.returns.push(AbiParam::new(I64));
+
+    module
+        .declare_function("malloc", Linkage::Import, &sig)
+        .expect("failed to declare malloc")
 }
 
-fn translate_expression(
+fn translate_expression<M: Module>(
     expr: &Expression,
     builder: &mut FunctionBuilder<'_>,
+    module: &mut M,
     ctx: &mut Ctx,
-) -> Value {
-    match expr {
+) -> Result<Value, CodegenError> {
+    let value = match expr {
         Expression::Literal(literal) => translate_literal(literal, builder),
         Expression::Assignment { ident, binding } => {
             let ty = expr.type_of(&HashMap::new());
-            translate_assignment(ident, binding, ty, builder, ctx);
+            translate_assignment(ident, binding, ty, expr.span(), builder, module, ctx)?;
             builder.ins().iconst(I64, 0) // placeholder nullptr
         }
         Expression::Identifier(name) => {
             if let Some(var) = ctx.get_variable(name) {
                 builder.use_var(var)
             } else {
-                panic!("undefined identifier {}", name);
+                return Err(CodegenError::UndefinedIdentifier {
+                    name: name.clone(),
+                    span: expr.span(),
+                });
             }
         }
         Expression::Infix {
             operation,
             lhs,
             rhs,
-        } => translate_infix(operation, lhs, rhs, builder, ctx),
+        } => {
+            let operand_ty = lhs.type_of(&HashMap::new());
+            translate_infix(operation, lhs, rhs, &operand_ty, builder, module, ctx)?
+        }
         Expression::Return(expr) => {
-            let return_val = translate_expression(expr, builder, ctx);
+            let return_val = translate_expression(expr, builder, module, ctx)?;
             builder.ins().return_(&[return_val]);
             return_val
         }
         Expression::Block(b) => {
-            translate_block(b, builder, ctx);
+            translate_block(b, builder, module, ctx)?;
             builder.ins().iconst(I64, 0) // placeholder nullptr
         }
+        Expression::If { cond, then, else_ } => {
+            let ty = expr.type_of(&HashMap::new());
+            let result_ty = type_to_cranelift(&ty, expr.span())?;
+            translate_if(cond, then, else_, result_ty, builder, module, ctx)?
+        }
+        Expression::While { cond, body } => translate_while(cond, body, builder, module, ctx)?,
+        Expression::Call { name, args } => {
+            let func_id = ctx
+                .get_func(name)
+                .ok_or_else(|| CodegenError::UndefinedFunction {
+                    name: name.clone(),
+                    span: expr.span(),
+                })?;
+            let func_ref = module.declare_func_in_func(func_id, builder.func);
+
+            let arg_vals = args
+                .iter()
+                .map(|arg| translate_expression(arg, builder, module, ctx))
+                .collect::<Result<Vec<Value>, CodegenError>>()?;
+
+            let call = builder.ins().call(func_ref, &arg_vals);
+            match builder.inst_results(call) {
+                [result] => *result,
+                _ => builder.ins().iconst(I64, 0), // placeholder nullptr, callee returns unit
+            }
+        }
+        Expression::RecordLiteral { name, fields } => {
+            let field_tys = match expr.type_of(&HashMap::new()) {
+                T::Record(_, field_tys) => field_tys,
+                _ => return Err(CodegenError::UnresolvedType { span: expr.span() }),
+            };
+            let layout = ctx.record_layout(name, &field_tys, expr.span())?;
+            let size = layout.size;
+            // resolve every field's offset up front so the loop below is
+            // free to borrow `ctx` mutably for `translate_expression`
+            let offsets = fields
+                .iter()
+                .map(|(field_name, field_expr)| {
+                    let (offset, _) = *layout.fields.get(field_name).ok_or_else(|| {
+                        CodegenError::UnknownField {
+                            name: field_name.clone(),
+                            span: expr.span(),
+                        }
+                    })?;
+                    Ok((offset, field_expr))
+                })
+                .collect::<Result<Vec<_>, CodegenError>>()?;
+
+            let malloc_id = ctx.malloc_id(module);
+            let malloc_ref = module.declare_func_in_func(malloc_id, builder.func);
+            let size_val = builder.ins().iconst(I64, size);
+            let call = builder.ins().call(malloc_ref, &[size_val]);
+            let base_ptr = builder.inst_results(call)[0];
+
+            for (offset, field_expr) in offsets {
+                let field_val = translate_expression(field_expr, builder, module, ctx)?;
+                builder
+                    .ins()
+                    .store(MemFlags::new(), field_val, base_ptr, offset);
+            }
+
+            base_ptr
+        }
+        Expression::FieldAccess { base, field } => {
+            let (record_name, field_tys) = match base.type_of(&HashMap::new()) {
+                T::Record(record_name, field_tys) => (record_name, field_tys),
+                _ => return Err(CodegenError::UnresolvedType { span: expr.span() }),
+            };
+            let (offset, field_ty) = *ctx
+                .record_layout(&record_name, &field_tys, expr.span())?
+                .fields
+                .get(field)
+                .ok_or_else(|| CodegenError::UnknownField {
+                    name: field.clone(),
+                    span: expr.span(),
+                })?;
+
+            let base_ptr = translate_expression(base, builder, module, ctx)?;
+            builder
+                .ins()
+                .load(field_ty, MemFlags::new(), base_ptr, offset)
+        }
         _ => unimplemented!(),
-    }
+    };
+
+    Ok(value)
 }
 
-fn translate_function(func: &Func, module: &mut ObjectModule) -> Result<()> {
-    let param_tys: Vec<Type> = func
-        .params
-        .iter()
-        .filter_map(|(_, ty)| type_to_cranelift(ty))
-        .collect();
+// builds the cranelift signature for a function declaration; shared by the
+// declaration pass and the body-generation pass so the two always agree
+fn func_signature<M: Module>(
+    func: &Func,
+    module: &M,
+) -> Result<(cranelift::codegen::ir::Signature, Vec<Type>), CodegenError> {
+    let mut param_tys = Vec::new();
+    for (_, ty) in &func.params {
+        if let Some(cranelift_ty) = type_to_cranelift(ty, func.span)? {
+            param_tys.push(cranelift_ty);
+        }
+    }
 
     let mut func_sig = module.make_signature();
     for ty in &param_tys {
         func_sig.params.push(AbiParam::new(*ty))
     }
 
-    if let Some(ty) = type_to_cranelift(&func.return_ty) {
+    if let Some(ty) = type_to_cranelift(&func.return_ty, func.span)? {
         func_sig.returns.push(AbiParam::new(ty));
     }
 
-    let func_id = module.declare_function(&func.name, Linkage::Export, &func_sig)?;
+    Ok((func_sig, param_tys))
+}
+
+fn translate_function<M: Module>(
+    func: &Func,
+    func_id: FuncId,
+    module: &mut M,
+    funcs: &HashMap<String, FuncId>,
+) -> Result<()> {
+    let (func_sig, param_tys) = func_signature(func, module)?;
+
     // individual context for the function
     let mut func_ctx = module.make_context();
-    func_ctx.func.signature = func_sig.clone();
+    func_ctx.func.signature = func_sig;
 
     // create the function builder context
     let mut fb_ctx = FunctionBuilderContext::new();
@@ -182,8 +654,9 @@ fn translate_function(func: &Func, module: &mut ObjectModule) -> Result<()> {
     builder.switch_to_block(block);
     builder.seal_block(block);
 
-    // translation level context to track variables inside the function
-    let mut ctx = Ctx::new();
+    // translation level context to track variables and forward-declared
+    // functions inside the function body
+    let mut ctx = Ctx::new(funcs.clone());
 
     for (idx, (name, _)) in func.params.iter().enumerate() {
         let param_var = ctx.declare_variable(name, &mut builder, param_tys[idx]);
@@ -192,7 +665,7 @@ fn translate_function(func: &Func, module: &mut ObjectModule) -> Result<()> {
     }
 
     for expr in &func.body.instructions {
-        let _ = translate_expression(expr, &mut builder, &mut ctx);
+        let _ = translate_expression(expr, &mut builder, module, &mut ctx)?;
     }
 
     builder.finalize();
@@ -201,6 +674,46 @@ fn translate_function(func: &Func, module: &mut ObjectModule) -> Result<()> {
     Ok(())
 }
 
+// declares and defines every top-level function of the AST into `module`,
+// in the two-pass order that makes forward references and mutual recursion
+// work, returning the name -> FuncId map so a backend can look up an entry
+// point afterwards
+fn translate_ast<M: Module>(
+    ast: Vec<Expression>,
+    module: &mut M,
+) -> Result<HashMap<String, FuncId>> {
+    // first pass: declare every function's signature up front so calls can
+    // resolve forward references and mutual recursion
+    let mut funcs = HashMap::new();
+    for expr in &ast {
+        match expr {
+            Expression::Function(func) => {
+                let (func_sig, _) = func_signature(func, module)?;
+                let func_id = module.declare_function(&func.name, Linkage::Export, &func_sig)?;
+                funcs.insert(func.name.clone(), func_id);
+            }
+            t => {
+                return Err(CodegenError::UnexpectedTopLevel { span: t.span() }.into());
+            }
+        }
+    }
+
+    // second pass: generate bodies now that every FuncId is known
+    for expr in ast {
+        match expr {
+            Expression::Function(func) => {
+                let func_id = funcs[&func.name];
+                translate_function(&func, func_id, module, &funcs)?;
+            }
+            t => {
+                return Err(CodegenError::UnexpectedTopLevel { span: t.span() }.into());
+            }
+        }
+    }
+
+    Ok(funcs)
+}
+
 pub fn translate(ast: Vec<Expression>, module_name: &str) -> Result<()> {
     let flags = settings::Flags::new(settings::builder());
     let isa_builder = cranelift_native::builder().expect("arch isnt supported");
@@ -211,18 +724,63 @@ pub fn translate(ast: Vec<Expression>, module_name: &str) -> Result<()> {
 
     let mut module = ObjectModule::new(object_builder);
 
-    for expr in ast {
-        match expr {
-            Expression::Function(func) => translate_function(&func, &mut module)?,
-            t => panic!("top level must be function, got {t:?}"),
-        }
-    }
+    translate_ast(ast, &mut module)?;
 
     let object = module.finish();
     std::fs::write(format!("{module_name}.o"), object.emit()?)?;
     Ok(())
 }
 
+// compiles `ast` with the Cranelift JIT backend and immediately calls its
+// `entry` function, returning the i64 it produces; this is the path a REPL
+// or `run` subcommand uses instead of going through an object file + linker
+pub fn jit_run(ast: Vec<Expression>, entry: &str) -> Result<i64> {
+    // the entry point's real return width, so the call below can sign- (not
+    // zero-) extend a 32-bit `int` into the `i64` this function hands back;
+    // resolved up front since `translate_ast` takes ownership of `ast`
+    let entry_return_ty = ast
+        .iter()
+        .find_map(|expr| match expr {
+            Expression::Function(func) if func.name == entry => {
+                Some(type_to_cranelift(&func.return_ty, func.span))
+            }
+            _ => None,
+        })
+        .unwrap_or_else(|| panic!("undefined entry point function {}", entry))?;
+
+    let jit_builder = JITBuilder::new(default_libcall_names()).expect("jit builder not supported");
+    let mut module = JITModule::new(jit_builder);
+
+    let funcs = translate_ast(ast, &mut module)?;
+    let func_id = *funcs
+        .get(entry)
+        .unwrap_or_else(|| panic!("undefined entry point function {}", entry));
+
+    module.finalize_definitions()?;
+
+    let code_ptr = module.get_finalized_function(func_id);
+
+    // the calling convention only writes as many bits as the declared return
+    // type occupies; reading all of them back through a blanket `-> i64`
+    // signature would zero- instead of sign-extend a negative `int` (I32),
+    // so dispatch on the real width and extend on the Rust side
+    let result = if entry_return_ty == Some(I32) {
+        let entry_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> i32>(code_ptr) };
+        entry_fn() as i64
+    } else if entry_return_ty == Some(I8) {
+        let entry_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> i8>(code_ptr) };
+        entry_fn() as i64
+    } else if entry_return_ty == Some(F64) {
+        let entry_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> f64>(code_ptr) };
+        entry_fn() as i64
+    } else {
+        let entry_fn = unsafe { std::mem::transmute::<_, extern "C" fn() -> i64>(code_ptr) };
+        entry_fn()
+    };
+
+    Ok(result)
+}
+
 pub fn generate() -> Result<()> {
     let flags = settings::Flags::new(settings::builder());
     let isa_builder = cranelift_native::builder().expect("arch isnt supported");
@@ -286,3 +844,276 @@ pub fn generate() -> Result<()> {
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn span() -> Span {
+        Span { start: 0, end: 0 }
+    }
+
+    // wraps a single no-arg `main` function returning an int, so each test
+    // only has to build the interesting `Expression`
+    fn main_returning(body: Expression) -> Vec<Expression> {
+        vec![Expression::Function(Func {
+            name: "main".to_string(),
+            params: vec![],
+            return_ty: T::BuiltIn(BuiltinType::Int),
+            span: span(),
+            body: BlockExpr {
+                instructions: vec![Expression::Return(Box::new(body))],
+            },
+        })]
+    }
+
+    #[test]
+    fn if_else_takes_the_true_branch() {
+        let ast = main_returning(Expression::If {
+            cond: Box::new(Expression::Literal(Literal::Bool(true))),
+            then: Box::new(Expression::Literal(Literal::Int(1))),
+            else_: Box::new(Expression::Literal(Literal::Int(2))),
+        });
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 1);
+    }
+
+    #[test]
+    fn if_else_takes_the_false_branch() {
+        let ast = main_returning(Expression::If {
+            cond: Box::new(Expression::Literal(Literal::Bool(false))),
+            then: Box::new(Expression::Literal(Literal::Int(1))),
+            else_: Box::new(Expression::Literal(Literal::Int(2))),
+        });
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 2);
+    }
+
+    // regression test for chunk0-2: a `while` body ending in `return` must
+    // not also emit a back-edge jump, or cranelift-frontend panics on a
+    // block that already has a terminator
+    #[test]
+    fn while_loop_with_early_return_does_not_panic() {
+        let ast = vec![Expression::Function(Func {
+            name: "main".to_string(),
+            params: vec![],
+            return_ty: T::BuiltIn(BuiltinType::Int),
+            span: span(),
+            body: BlockExpr {
+                instructions: vec![Expression::While {
+                    cond: Box::new(Expression::Literal(Literal::Bool(true))),
+                    body: Box::new(Expression::Return(Box::new(Expression::Literal(
+                        Literal::Int(42),
+                    )))),
+                }],
+            },
+        })];
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 42);
+    }
+
+    #[test]
+    fn short_circuit_or_skips_rhs_when_lhs_is_true() {
+        let ast = main_returning(Expression::If {
+            cond: Box::new(Expression::Infix {
+                operation: Bop::Or,
+                lhs: Box::new(Expression::Literal(Literal::Bool(true))),
+                rhs: Box::new(Expression::Literal(Literal::Bool(false))),
+            }),
+            then: Box::new(Expression::Literal(Literal::Int(1))),
+            else_: Box::new(Expression::Literal(Literal::Int(0))),
+        });
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 1);
+    }
+
+    #[test]
+    fn short_circuit_and_is_false_when_lhs_is_false() {
+        let ast = main_returning(Expression::If {
+            cond: Box::new(Expression::Infix {
+                operation: Bop::And,
+                lhs: Box::new(Expression::Literal(Literal::Bool(false))),
+                rhs: Box::new(Expression::Literal(Literal::Bool(true))),
+            }),
+            then: Box::new(Expression::Literal(Literal::Int(1))),
+            else_: Box::new(Expression::Literal(Literal::Int(0))),
+        });
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 0);
+    }
+
+    #[test]
+    fn record_literal_field_round_trips_through_storage() {
+        let ast = main_returning(Expression::FieldAccess {
+            base: Box::new(Expression::RecordLiteral {
+                name: "Point".to_string(),
+                fields: vec![
+                    ("x".to_string(), Expression::Literal(Literal::Int(7))),
+                    ("y".to_string(), Expression::Literal(Literal::Int(9))),
+                ],
+            }),
+            field: "y".to_string(),
+        });
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 9);
+    }
+
+    // regression test for chunk0-1/chunk0-2: a while body that diverges via
+    // a nested if/else (rather than a bare `return`) must not emit a
+    // back-edge jump into the already-terminated arm block
+    #[test]
+    fn while_loop_with_diverging_nested_if_else_does_not_panic() {
+        let ast = vec![Expression::Function(Func {
+            name: "main".to_string(),
+            params: vec![],
+            return_ty: T::BuiltIn(BuiltinType::Int),
+            span: span(),
+            body: BlockExpr {
+                instructions: vec![Expression::While {
+                    cond: Box::new(Expression::Literal(Literal::Bool(true))),
+                    body: Box::new(Expression::If {
+                        cond: Box::new(Expression::Literal(Literal::Bool(true))),
+                        then: Box::new(Expression::Return(Box::new(Expression::Literal(
+                            Literal::Int(1),
+                        )))),
+                        else_: Box::new(Expression::Return(Box::new(Expression::Literal(
+                            Literal::Int(2),
+                        )))),
+                    }),
+                }],
+            },
+        })];
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 1);
+    }
+
+    // chunk0-3: a function declared earlier in the AST can still call one
+    // declared later, since declaration happens in a pass separate from
+    // body generation
+    #[test]
+    fn forward_reference_to_a_later_declared_function_resolves() {
+        let ast = vec![
+            Expression::Function(Func {
+                name: "a".to_string(),
+                params: vec![],
+                return_ty: T::BuiltIn(BuiltinType::Int),
+                span: span(),
+                body: BlockExpr {
+                    instructions: vec![Expression::Return(Box::new(Expression::Call {
+                        name: "b".to_string(),
+                        args: vec![],
+                    }))],
+                },
+            }),
+            Expression::Function(Func {
+                name: "b".to_string(),
+                params: vec![],
+                return_ty: T::BuiltIn(BuiltinType::Int),
+                span: span(),
+                body: BlockExpr {
+                    instructions: vec![Expression::Return(Box::new(Expression::Literal(
+                        Literal::Int(99),
+                    )))],
+                },
+            }),
+        ];
+
+        assert_eq!(jit_run(ast, "a").unwrap(), 99);
+    }
+
+    // chunk0-7: float arithmetic, dispatched through translate_infix's
+    // is_float path; also exercises jit_run's F64 entry-return dispatch
+    #[test]
+    fn float_arithmetic_round_trips_through_jit_run() {
+        let ast = vec![Expression::Function(Func {
+            name: "main".to_string(),
+            params: vec![],
+            return_ty: T::BuiltIn(BuiltinType::Float),
+            span: span(),
+            body: BlockExpr {
+                instructions: vec![Expression::Return(Box::new(Expression::Infix {
+                    operation: Bop::Plus,
+                    lhs: Box::new(Expression::Literal(Literal::Float(1.5))),
+                    rhs: Box::new(Expression::Literal(Literal::Float(2.5))),
+                }))],
+            },
+        })];
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 4);
+    }
+
+    // chunk0-7: int comparison operators beyond eq/neq
+    #[test]
+    fn int_less_than_comparison_produces_expected_bool() {
+        let ast = vec![Expression::Function(Func {
+            name: "main".to_string(),
+            params: vec![],
+            return_ty: T::BuiltIn(BuiltinType::Bool),
+            span: span(),
+            body: BlockExpr {
+                instructions: vec![Expression::Return(Box::new(Expression::Infix {
+                    operation: Bop::Lt,
+                    lhs: Box::new(Expression::Literal(Literal::Int(3))),
+                    rhs: Box::new(Expression::Literal(Literal::Int(5))),
+                }))],
+            },
+        })];
+
+        assert_eq!(jit_run(ast, "main").unwrap(), 1);
+    }
+
+    // chunk0-5: codegen errors must come back as `Err`, not panic the
+    // compiler; `translate_ast` is exercised directly against a fresh
+    // `JITModule` so these don't depend on an entry point resolving
+    fn translate_err(ast: Vec<Expression>) -> CodegenError {
+        let mut module = JITModule::new(JITBuilder::new(default_libcall_names()).unwrap());
+        translate_ast(ast, &mut module)
+            .unwrap_err()
+            .downcast_ref::<CodegenError>()
+            .expect("translate_ast error should be a CodegenError")
+            .clone()
+    }
+
+    #[test]
+    fn undefined_identifier_is_a_codegen_error_not_a_panic() {
+        let ast = main_returning(Expression::Identifier("nope".to_string()));
+
+        assert!(matches!(
+            translate_err(ast),
+            CodegenError::UndefinedIdentifier { .. }
+        ));
+    }
+
+    #[test]
+    fn undefined_function_call_is_a_codegen_error_not_a_panic() {
+        let ast = main_returning(Expression::Call {
+            name: "does_not_exist".to_string(),
+            args: vec![],
+        });
+
+        assert!(matches!(
+            translate_err(ast),
+            CodegenError::UndefinedFunction { .. }
+        ));
+    }
+
+    // chunk0-6: accessing a field the record's own literal didn't define
+    #[test]
+    fn unknown_record_field_access_is_a_codegen_error() {
+        let ast = main_returning(Expression::FieldAccess {
+            base: Box::new(Expression::RecordLiteral {
+                name: "Point".to_string(),
+                fields: vec![
+                    ("x".to_string(), Expression::Literal(Literal::Int(7))),
+                    ("y".to_string(), Expression::Literal(Literal::Int(9))),
+                ],
+            }),
+            field: "z".to_string(),
+        });
+
+        assert!(matches!(
+            translate_err(ast),
+            CodegenError::UnknownField { .. }
+        ));
+    }
+}